@@ -2,7 +2,7 @@ mod frontmatter;
 mod references;
 
 use std::{
-    collections::HashSet,
+    collections::{HashSet, VecDeque},
     ffi::OsString,
     fs,
     path::{Path, PathBuf},
@@ -10,8 +10,11 @@ use std::{
 
 pub use frontmatter::Frontmatter;
 use ignore::WalkBuilder;
+use pathdiff::diff_paths;
 pub use pulldown_cmark;
-use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{CowStr, Event, LinkType, MetadataBlockKind, Options, Parser, Tag, TagEnd};
+use pulldown_cmark_to_cmark::cmark;
+use rayon::prelude::*;
 use references::{ObsidianNoteReference, RefParser, RefParserState, RefType};
 use serde_yaml::Value;
 use snafu::{ResultExt, Snafu};
@@ -39,6 +42,13 @@ pub enum CopyError {
     /// This occurs when an operation is requested on a file or directory which doesn't exist.
     PathDoesNotExist { path: PathBuf },
 
+    #[snafu(display("failed to create directory `{}`", path.display()))]
+    /// This occurs when creating a destination directory fails.
+    CreateDirError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[snafu(display("Encountered an error trying to walk `{}`", path.display()))]
     /// This occurs when an error is encountered while trying to walk a directory.
     WalkDirError {
@@ -53,10 +63,117 @@ pub enum CopyError {
         #[snafu(source(from(serde_yaml::Error, Box::new)))]
         source: Box<serde_yaml::Error>,
     },
+
+    #[snafu(display("Failed to render markdown for `{}`", path.display()))]
+    /// This occurs when re-serializing a note's event stream back to CommonMark fails.
+    RenderError {
+        path: PathBuf,
+        source: std::fmt::Error,
+    },
 }
 
 type Result<T, E = CopyError> = std::result::Result<T, E>;
 
+/// Configures how a vault directory is walked when looking for files to index.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Whether hidden files and directories should be included. Defaults to `true`.
+    pub hidden: bool,
+    /// Whether `.gitignore` files should be respected. Defaults to `true`. This does not affect
+    /// `.git/info/exclude` or the global git ignore file, which are respected either way.
+    pub respect_gitignore: bool,
+    /// The name of an additional custom ignore file to respect, alongside the standard ones.
+    pub custom_ignore_filename: Option<String>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            hidden: true,
+            respect_gitignore: true,
+            custom_ignore_filename: None,
+        }
+    }
+}
+
+/// Controls whether a note's YAML frontmatter is retained in the copied output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrontmatterStrategy {
+    /// Retain a note's frontmatter if it has any, and leave notes without frontmatter as-is.
+    #[default]
+    Auto,
+    /// Always emit frontmatter, synthesizing an empty block for notes which didn't have one.
+    Always,
+    /// Strip frontmatter from every note, regardless of whether it originally had any.
+    Never,
+}
+
+/// A parsed markdown document, as a stream of [`pulldown_cmark`] events.
+///
+/// Postprocessors receive and may mutate this alongside a [`Context`].
+pub type MarkdownEvents<'a> = Vec<Event<'a>>;
+
+/// Gives a postprocessor access to a note's source path, destination and frontmatter.
+///
+/// The destination and frontmatter may be mutated; doing so affects where the note is written
+/// and, in the case of frontmatter, has no effect on the rendered output unless a postprocessor
+/// also edits the event stream, or [`FrontmatterStrategy::Always`] synthesizes a block from it
+/// for a note which had none.
+pub struct Context {
+    source: PathBuf,
+    destination: PathBuf,
+    frontmatter: Frontmatter,
+}
+
+impl Context {
+    fn new(source: PathBuf, destination: PathBuf, frontmatter: Frontmatter) -> Context {
+        Context {
+            source,
+            destination,
+            frontmatter,
+        }
+    }
+
+    /// The note's original path within the vault.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// The path the note will be written to. Postprocessors may rewrite this to change where
+    /// the note ends up.
+    pub fn destination(&mut self) -> &mut PathBuf {
+        &mut self.destination
+    }
+
+    /// The note's parsed YAML frontmatter.
+    pub fn frontmatter(&mut self) -> &mut Frontmatter {
+        &mut self.frontmatter
+    }
+}
+
+/// Indicates to the copier how to proceed after a postprocessor has run.
+pub enum PostprocessorResult {
+    /// Continue running the remaining postprocessors.
+    Continue,
+    /// Stop running postprocessors for this note, but still write it out.
+    StopHere,
+    /// Stop running postprocessors for this note, and don't copy it at all.
+    StopAndSkipNote,
+}
+
+/// A postprocessor is run on every markdown note after indexing, just before it's written to
+/// `destination`. It may inspect or mutate the note's [`Context`] and [`MarkdownEvents`].
+pub type Postprocessor =
+    dyn for<'a> Fn(&mut Context, &mut MarkdownEvents<'a>) -> PostprocessorResult + Send + Sync;
+
+/// The outcome of indexing a single note, produced independently of any other note so that
+/// indexing can be parallelized.
+struct NoteIndexResult {
+    included: bool,
+    src: PathBuf,
+    found_attachments: HashSet<PathBuf>,
+}
+
 pub struct Copier {
     root: PathBuf,
     destination: PathBuf,
@@ -64,6 +181,13 @@ pub struct Copier {
     exclude_tags: HashSet<String>,
     to_copy: HashSet<PathBuf>,
     vault_contents: HashSet<PathBuf>,
+    postprocessors: Vec<Box<Postprocessor>>,
+    follow_links: bool,
+    max_link_depth: usize,
+    ignore_frontmatter_keyword: String,
+    walk_options: WalkOptions,
+    rewrite_links: bool,
+    frontmatter_strategy: FrontmatterStrategy,
 }
 
 /// `Copier` provides the main interface to this library.
@@ -81,6 +205,13 @@ impl Copier {
             exclude_tags: HashSet::new(),
             to_copy: HashSet::new(),
             vault_contents: HashSet::new(),
+            postprocessors: Vec::new(),
+            follow_links: true,
+            max_link_depth: 10,
+            ignore_frontmatter_keyword: String::from("private"),
+            walk_options: WalkOptions::default(),
+            rewrite_links: false,
+            frontmatter_strategy: FrontmatterStrategy::default(),
         }
     }
 
@@ -96,53 +227,276 @@ impl Copier {
         self
     }
 
+    /// Register a postprocessor to run on every markdown note, in the order added, just before
+    /// it's written to `destination`.
+    pub fn add_postprocessor<F>(&mut self, f: F) -> &mut Self
+    where
+        F: for<'a> Fn(&mut Context, &mut MarkdownEvents<'a>) -> PostprocessorResult
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.postprocessors.push(Box::new(f));
+        self
+    }
+
+    /// Whether notes linked to by an included note (e.g. via `[[Another Note]]`) should
+    /// transitively be pulled in too, even if they don't carry an included tag themselves.
+    /// Defaults to `true`. See also [`Copier::max_link_depth`].
+    pub fn follow_links(&mut self, follow_links: bool) -> &mut Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    /// The maximum number of hops to follow when [`Copier::follow_links`] is enabled. Defaults
+    /// to 10.
+    pub fn max_link_depth(&mut self, max_link_depth: usize) -> &mut Self {
+        self.max_link_depth = max_link_depth;
+        self
+    }
+
+    /// The frontmatter key whose boolean value, if `true`, excludes a note regardless of its
+    /// tags. Defaults to `"private"`.
+    pub fn ignore_frontmatter_keyword(&mut self, keyword: String) -> &mut Self {
+        self.ignore_frontmatter_keyword = keyword;
+        self
+    }
+
+    /// Configures how the vault directory is walked, e.g. whether hidden files or
+    /// `.gitignore`d files are indexed. See [`WalkOptions`].
+    pub fn walk_options(&mut self, walk_options: WalkOptions) -> &mut Self {
+        self.walk_options = walk_options;
+        self
+    }
+
+    /// Whether `[[note]]`/`![[embed]]` wikilinks should be rewritten into standard CommonMark
+    /// links/images pointing at the copied file's relative location. Defaults to `false`, since
+    /// the resulting markdown is no longer meant to be opened in Obsidian itself.
+    pub fn rewrite_links(&mut self, rewrite_links: bool) -> &mut Self {
+        self.rewrite_links = rewrite_links;
+        self
+    }
+
+    /// Controls whether a note's YAML frontmatter is retained in the copied output. Defaults to
+    /// [`FrontmatterStrategy::Auto`].
+    pub fn frontmatter_strategy(&mut self, strategy: FrontmatterStrategy) -> &mut Self {
+        self.frontmatter_strategy = strategy;
+        self
+    }
+
     /// Processes vault to determines files which should be copied.
     pub fn index(&mut self) -> Result<()> {
-        self.vault_contents = vault_contents(&self.root)?;
-        self.vault_contents
-            .clone()
-            .into_iter()
+        self.vault_contents = vault_contents(&self.root, &self.walk_options)?;
+
+        let markdown_files: Vec<&PathBuf> = self
+            .vault_contents
+            .iter()
             .filter(|file| is_markdown_file(file))
-            .try_for_each(|file| self.test_and_add_note(file))?;
+            .collect();
+
+        let results: Vec<NoteIndexResult> = markdown_files
+            .par_iter()
+            .map(|file| self.test_note(file))
+            .collect::<Result<_>>()?;
+
+        let mut included_notes: Vec<PathBuf> = Vec::new();
+
+        for result in results {
+            if result.included {
+                self.to_copy.insert(result.src.clone());
+                included_notes.push(result.src);
+                // Attachments (images, PDFs, etc.) are always copied, since they have no tags of
+                // their own to be included by. Referenced notes are only pulled in transitively,
+                // via `follow_linked_notes`, so that `follow_links`/`max_link_depth` are enforced
+                // uniformly from the first hop onwards.
+                for reference in result.found_attachments {
+                    if !is_markdown_file(&reference) {
+                        self.to_copy.insert(reference);
+                    }
+                }
+            }
+        }
+
+        if self.follow_links {
+            self.follow_linked_notes(included_notes)?;
+        }
+
         Ok(())
     }
 
-    fn test_and_add_note(&mut self, src: PathBuf) -> Result<()> {
-        let content = fs::read_to_string(&src).context(ReadSnafu { path: &src })?;
-        let mut frontmatter_str = String::new();
-        // let mut found_attachments: HashSet<PathBuf> = HashSet::new();
+    /// Breadth-first search over the wikilinks of every tag-included note, enqueueing any note or
+    /// attachment they reference that hasn't been visited yet. Bounded by `max_link_depth` (depth
+    /// `0` means no links are followed past the tag-included notes themselves) and a `visited`
+    /// set so that link cycles can't blow the stack or explode the copy set.
+    fn follow_linked_notes(&mut self, included_notes: Vec<PathBuf>) -> Result<()> {
+        let mut visited: HashSet<PathBuf> = HashSet::new();
+        let mut worklist: VecDeque<(PathBuf, usize)> =
+            included_notes.into_iter().map(|file| (file, 0)).collect();
 
-        let parser_options = Options::ENABLE_MATH
-            | Options::ENABLE_TABLES
-            | Options::ENABLE_FOOTNOTES
-            | Options::ENABLE_TASKLISTS
-            | Options::ENABLE_STRIKETHROUGH
-            | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+        while let Some((src, depth)) = worklist.pop_front() {
+            if !visited.insert(src.clone()) || depth >= self.max_link_depth {
+                continue;
+            }
 
-        let mut parser = Parser::new_ext(&content, parser_options);
-        let mut ref_parser = RefParser::new();
-        let mut events: Vec<Event> = Vec::new();
-        let mut buffer = Vec::with_capacity(5);
+            let content = fs::read_to_string(&src).context(ReadSnafu { path: &src })?;
+            let (_, events) = parse_events(&content, &src);
+
+            for reference in self.find_references(&events) {
+                let is_new = self.to_copy.insert(reference.clone());
+                if is_new && is_markdown_file(&reference) {
+                    worklist.push_back((reference, depth + 1));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a single note and determines whether it should be included, without touching any
+    /// shared state. This lets [`Copier::index`] run it across notes in parallel and fold the
+    /// results in afterwards; `reference_to_path` only reads the immutable `vault_contents`, so
+    /// it's safe to call from multiple threads at once.
+    fn test_note(&self, src: &Path) -> Result<NoteIndexResult> {
+        let content = fs::read_to_string(src).context(ReadSnafu { path: src })?;
+        let (frontmatter_str, events) = parse_events(&content, src);
+        let frontmatter = frontmatter::from_str(&frontmatter_str)
+            .context(FrontmatterDecodeSnafu { path: src })?;
+        let found_attachments = self.find_references(&events);
+
+        let tags: Vec<String> = match frontmatter.get("tags") {
+            Some(Value::Sequence(tags)) => tags
+                .iter()
+                .filter_map(|tag| {
+                    if let Value::String(s) = tag {
+                        Some(s.to_string())
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let ignored = matches!(
+            frontmatter.get(self.ignore_frontmatter_keyword.as_str()),
+            Some(Value::Bool(true))
+        );
+
+        let included = !ignored
+            && tags.iter().any(|tag| self.include_tags.contains(tag))
+            && !tags.iter().any(|tag| self.exclude_tags.contains(tag));
+
+        Ok(NoteIndexResult {
+            included,
+            src: src.to_path_buf(),
+            found_attachments,
+        })
+    }
+
+    /// Walks a parsed event stream looking for Obsidian references (`[[note]]`, `![[embed]]`)
+    /// and resolves each one to a path within the vault.
+    fn find_references(&self, events: &[Event]) -> HashSet<PathBuf> {
         let mut found_attachments: HashSet<PathBuf> = HashSet::new();
+        let mut ref_parser = RefParser::new();
 
-        'outer: while let Some(event) = parser.next() {
-            // Collect all frontmatter to string in one sweep
-            if matches!(event, Event::Start(Tag::MetadataBlock(_kind))) {
-                for event in parser.by_ref() {
-                    match event {
-                        Event::Text(cowstr) => frontmatter_str.push_str(&cowstr),
-                        Event::End(TagEnd::MetadataBlock(_kind)) => {
-                            continue 'outer;
+        for event in events {
+            if ref_parser.state == RefParserState::Resetting {
+                ref_parser.reset();
+            }
+            match ref_parser.state {
+                RefParserState::NoState => match event {
+                    Event::Text(CowStr::Borrowed("![")) => {
+                        ref_parser.ref_type = Some(RefType::Embed);
+                        ref_parser.transition(RefParserState::ExpectSecondOpenBracket);
+                    }
+                    Event::Text(CowStr::Borrowed("[")) => {
+                        ref_parser.ref_type = Some(RefType::Link);
+                        ref_parser.transition(RefParserState::ExpectSecondOpenBracket);
+                    }
+                    _ => {}
+                },
+                RefParserState::ExpectSecondOpenBracket => match event {
+                    Event::Text(CowStr::Borrowed("[")) => {
+                        ref_parser.transition(RefParserState::ExpectRefText);
+                    }
+                    _ => {
+                        ref_parser.transition(RefParserState::Resetting);
+                    }
+                },
+                RefParserState::ExpectRefText => match event {
+                    Event::Text(CowStr::Borrowed("]")) => {
+                        ref_parser.transition(RefParserState::Resetting);
+                    }
+                    Event::Text(text) => {
+                        ref_parser.ref_text.push_str(text);
+                        ref_parser.transition(RefParserState::ExpectRefTextOrCloseBracket);
+                    }
+                    _ => {
+                        ref_parser.transition(RefParserState::Resetting);
+                    }
+                },
+                RefParserState::ExpectRefTextOrCloseBracket => match event {
+                    Event::Text(CowStr::Borrowed("]")) => {
+                        ref_parser.transition(RefParserState::ExpectFinalCloseBracket);
+                    }
+                    Event::Text(text) => {
+                        ref_parser.ref_text.push_str(text);
+                    }
+                    _ => {
+                        ref_parser.transition(RefParserState::Resetting);
+                    }
+                },
+                RefParserState::ExpectFinalCloseBracket => match event {
+                    Event::Text(CowStr::Borrowed("]")) => {
+                        let reference = ObsidianNoteReference::from_str(&ref_parser.ref_text);
+                        if let Some(attachment) = self.reference_to_path(reference) {
+                            found_attachments.insert(attachment);
                         }
-                        _ => panic!(
-                            "Encountered an unexpected event while processing frontmatter in {}.",
-                            src.display()
-                        ),
                     }
-                }
+                    _ => {
+                        ref_parser.transition(RefParserState::Resetting);
+                    }
+                },
+                RefParserState::Resetting => panic!(
+                    "Reached Resetting state, but it should have been handled prior to this match block"
+                ),
             }
+        }
+
+        found_attachments
+    }
+
+    fn reference_to_path(&self, reference: ObsidianNoteReference) -> Option<PathBuf> {
+        reference
+            .file
+            .and_then(|filename| lookup_filename_in_vault(filename, &self.vault_contents))
+            .cloned()
+    }
+
+    /// Maps a source path within the vault to the path it will be copied to.
+    fn destination_for(&self, src: &Path) -> PathBuf {
+        let relative_path = src
+            .strip_prefix(&self.root)
+            .expect("walked files should be nested under root");
+        self.destination.join(relative_path)
+    }
+
+    /// Rewrites `[[note]]`/`![[embed]]` references in `events` into standard CommonMark
+    /// links/images pointing at the copied file's relative location, using the same
+    /// [`RefParser`] state machine as [`Copier::find_references`] to capture each reference
+    /// span. References which can't be resolved within the vault are left untouched.
+    fn rewrite_references<'a>(
+        &self,
+        events: MarkdownEvents<'a>,
+        note_destination: &Path,
+    ) -> MarkdownEvents<'a> {
+        let mut output: MarkdownEvents = Vec::with_capacity(events.len());
+        let mut buffer: MarkdownEvents = Vec::with_capacity(5);
+        let mut ref_parser = RefParser::new();
+
+        for event in events {
             if ref_parser.state == RefParserState::Resetting {
-                events.append(&mut buffer);
+                output.append(&mut buffer);
                 buffer.clear();
                 ref_parser.reset();
             }
@@ -158,7 +512,7 @@ impl Copier {
                         ref_parser.transition(RefParserState::ExpectSecondOpenBracket);
                     }
                     _ => {
-                        events.push(event);
+                        output.push(event);
                         buffer.clear();
                     }
                 },
@@ -196,74 +550,160 @@ impl Copier {
                 RefParserState::ExpectFinalCloseBracket => match event {
                     Event::Text(CowStr::Borrowed("]")) => {
                         let reference = ObsidianNoteReference::from_str(&ref_parser.ref_text);
-                        if let Some(attachment) = self.reference_to_path(reference) {
-                            found_attachments.insert(attachment);
+                        match self.rewritten_reference_events(&reference, &ref_parser, note_destination) {
+                            Some(rewritten) => output.extend(rewritten),
+                            None => output.append(&mut buffer),
                         }
-                    },
+                        buffer.clear();
+                        ref_parser.reset();
+                    }
                     _ => {
                         ref_parser.transition(RefParserState::Resetting);
                     }
                 },
-                RefParserState::Resetting => panic!("Reached Resetting state, but it should have been handled prior to this match block"),
+                RefParserState::Resetting => panic!(
+                    "Reached Resetting state, but it should have been handled prior to this match block"
+                ),
             }
         }
+        output.append(&mut buffer);
 
-        let frontmatter = frontmatter::from_str(&frontmatter_str)
-            .context(FrontmatterDecodeSnafu { path: &src })?;
-
-        let tags: Vec<String> = match frontmatter.get("tags") {
-            Some(Value::Sequence(tags)) => tags
-                .iter()
-                .filter_map(|tag| {
-                    if let Value::String(s) = tag {
-                        Some(s.to_string())
-                    } else {
-                        None
-                    }
-                })
-                .collect(),
-            _ => Vec::new(),
-        };
+        output
+    }
 
-        let include = tags.iter().any(|tag| self.include_tags.contains(tag))
-            && !tags.iter().any(|tag| self.exclude_tags.contains(tag));
+    /// Builds the CommonMark link/image events for a single resolved reference, or `None` if the
+    /// reference doesn't point at anything in the vault (in which case the original text should
+    /// be left untouched).
+    fn rewritten_reference_events(
+        &self,
+        reference: &ObsidianNoteReference,
+        ref_parser: &RefParser,
+        note_destination: &Path,
+    ) -> Option<MarkdownEvents<'static>> {
+        let target = self.reference_to_path(*reference)?;
+        let target_destination = self.destination_for(&target);
+        let relative_dir = note_destination.parent().unwrap_or(Path::new(""));
+        let relative_path =
+            diff_paths(&target_destination, relative_dir).unwrap_or(target_destination.clone());
 
-        if include {
-            self.to_copy.insert(src);
-            self.to_copy.extend(found_attachments);
+        let mut url = encode_link_path(&relative_path);
+        if let Some(section) = reference.section {
+            url.push('#');
+            url.push_str(&slugify_fragment(section));
         }
 
-        Ok(())
-    }
+        let label = reference
+            .label
+            .map(ToString::to_string)
+            .or_else(|| reference.file.map(ToString::to_string))
+            .or_else(|| {
+                target
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
 
-    fn reference_to_path(&self, reference: ObsidianNoteReference) -> Option<PathBuf> {
-        reference
-            .file
-            .and_then(|filename| lookup_filename_in_vault(filename, &self.vault_contents))
-            .cloned()
+        let (start, end) = match &ref_parser.ref_type {
+            Some(RefType::Embed) => (
+                Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url: CowStr::from(url),
+                    title: CowStr::from(""),
+                    id: CowStr::from(""),
+                },
+                TagEnd::Image,
+            ),
+            _ => (
+                Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: CowStr::from(url),
+                    title: CowStr::from(""),
+                    id: CowStr::from(""),
+                },
+                TagEnd::Link,
+            ),
+        };
+
+        Some(vec![
+            Event::Start(start),
+            Event::Text(CowStr::from(label)),
+            Event::End(end),
+        ])
     }
 
     pub fn copy(self) -> Result<()> {
-        for file in self.to_copy {
+        for file in &self.to_copy {
             let relative_path = file
-                .strip_prefix(self.root.clone())
+                .strip_prefix(&self.root)
                 .expect("walked files should be nested under root")
                 .to_path_buf();
-            let destination = &self.destination.join(relative_path);
-            fs::copy(&file, destination).context(CopySnafu {
-                from: file,
-                to: destination,
-            })?;
+            let destination = self.destination.join(relative_path);
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent).context(CreateDirSnafu { path: parent })?;
+            }
+
+            if is_markdown_file(file) {
+                self.copy_note(file, destination)?;
+            } else {
+                fs::copy(file, &destination).context(CopySnafu {
+                    from: file.clone(),
+                    to: destination,
+                })?;
+            }
         }
         Ok(())
     }
+
+    /// Parses a single markdown note, runs it through the registered postprocessors, and writes
+    /// the resulting CommonMark to `destination`.
+    fn copy_note(&self, src: &Path, destination: PathBuf) -> Result<()> {
+        let content = fs::read_to_string(src).context(ReadSnafu { path: src })?;
+        let (frontmatter_str, mut events) = parse_events(&content, src);
+        let frontmatter =
+            frontmatter::from_str(&frontmatter_str).context(FrontmatterDecodeSnafu { path: src })?;
+
+        let mut context = Context::new(src.to_path_buf(), destination, frontmatter);
+
+        for postprocessor in &self.postprocessors {
+            match postprocessor(&mut context, &mut events) {
+                PostprocessorResult::Continue => continue,
+                PostprocessorResult::StopHere => break,
+                PostprocessorResult::StopAndSkipNote => return Ok(()),
+            }
+        }
+
+        if self.rewrite_links {
+            let note_destination = context.destination().clone();
+            events = self.rewrite_references(events, &note_destination);
+        }
+
+        apply_frontmatter_strategy(&mut events, context.frontmatter(), self.frontmatter_strategy);
+
+        let mut rendered = String::new();
+        cmark(events.iter(), &mut rendered).context(RenderSnafu { path: src })?;
+
+        fs::write(context.destination(), rendered).context(CopySnafu {
+            from: src.to_path_buf(),
+            to: context.destination().clone(),
+        })?;
+
+        Ok(())
+    }
 }
 
 /// `vault_contents` returns all of the files in an Obsidian vault located at the root, except
-/// those ignored.
-pub fn vault_contents(root: &Path) -> Result<HashSet<PathBuf>> {
+/// those ignored per `walk_options`.
+pub fn vault_contents(root: &Path, walk_options: &WalkOptions) -> Result<HashSet<PathBuf>> {
     let mut contents = HashSet::new();
-    let walker = WalkBuilder::new(root).hidden(false).build();
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!walk_options.hidden)
+        .git_ignore(walk_options.respect_gitignore);
+    if let Some(custom_ignore_filename) = &walk_options.custom_ignore_filename {
+        builder.add_custom_ignore_filename(custom_ignore_filename);
+    }
+    let walker = builder.build();
     for entry in walker {
         let entry = entry.context(WalkDirSnafu { path: root })?;
         let path = entry.path();
@@ -274,6 +714,127 @@ pub fn vault_contents(root: &Path) -> Result<HashSet<PathBuf>> {
     Ok(contents)
 }
 
+/// Parses `content` into a frontmatter string and a stream of markdown events, which can either
+/// be scanned for references or re-serialized back to CommonMark.
+fn parse_events<'a>(content: &'a str, src: &Path) -> (String, MarkdownEvents<'a>) {
+    let parser_options = Options::ENABLE_MATH
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut parser = Parser::new_ext(content, parser_options);
+    let mut frontmatter_str = String::new();
+    let mut events: MarkdownEvents = Vec::new();
+
+    'outer: while let Some(event) = parser.next() {
+        if let Event::Start(Tag::MetadataBlock(_)) = event {
+            events.push(event);
+            for event in parser.by_ref() {
+                match event {
+                    Event::Text(ref cowstr) => frontmatter_str.push_str(cowstr),
+                    Event::End(TagEnd::MetadataBlock(_)) => {
+                        events.push(event);
+                        continue 'outer;
+                    }
+                    _ => panic!(
+                        "Encountered an unexpected event while processing frontmatter in {}.",
+                        src.display()
+                    ),
+                }
+                events.push(event);
+            }
+        } else {
+            events.push(event);
+        }
+    }
+
+    (frontmatter_str, events)
+}
+
+/// Percent-encodes the characters in a relative link path which would otherwise break a
+/// CommonMark link destination (spaces, parens, `%` itself, and control characters), converting
+/// path separators to `/` along the way.
+fn encode_link_path(path: &Path) -> String {
+    path.components()
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_string_lossy()
+                .chars()
+                .map(|c| match c {
+                    ' ' => "%20".to_string(),
+                    '(' => "%28".to_string(),
+                    ')' => "%29".to_string(),
+                    '%' => "%25".to_string(),
+                    c if c.is_control() => format!("%{:02X}", c as u32),
+                    c => c.to_string(),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Slugifies a `#section` fragment the way Obsidian/GitHub-flavoured markdown headings are
+/// anchored: lowercased, with runs of non-alphanumeric characters collapsed to a single `-`.
+fn slugify_fragment(section: &str) -> String {
+    let mut slug = String::with_capacity(section.len());
+    let mut last_was_dash = false;
+    for c in section.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+            last_was_dash = false;
+        } else if !last_was_dash {
+            slug.push('-');
+            last_was_dash = true;
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Applies a [`FrontmatterStrategy`] to a note's parsed event stream before it's rendered,
+/// adding or removing its YAML metadata block as needed.
+fn apply_frontmatter_strategy(
+    events: &mut MarkdownEvents<'_>,
+    frontmatter: &Frontmatter,
+    strategy: FrontmatterStrategy,
+) {
+    let metadata_block_start = events
+        .iter()
+        .position(|event| matches!(event, Event::Start(Tag::MetadataBlock(_))));
+
+    match strategy {
+        FrontmatterStrategy::Auto => {}
+        FrontmatterStrategy::Never => {
+            if let Some(start) = metadata_block_start {
+                if let Some(end_offset) = events[start..]
+                    .iter()
+                    .position(|event| matches!(event, Event::End(TagEnd::MetadataBlock(_))))
+                {
+                    events.drain(start..=start + end_offset);
+                }
+            }
+        }
+        FrontmatterStrategy::Always => {
+            if metadata_block_start.is_none() {
+                if let Ok(yaml) = serde_yaml::to_string(frontmatter) {
+                    let kind = MetadataBlockKind::YamlStyle;
+                    events.splice(
+                        0..0,
+                        [
+                            Event::Start(Tag::MetadataBlock(kind)),
+                            Event::Text(CowStr::from(yaml)),
+                            Event::End(TagEnd::MetadataBlock(kind)),
+                        ],
+                    );
+                }
+            }
+        }
+    }
+}
+
 fn is_markdown_file(file: &Path) -> bool {
     let no_ext = OsString::new();
     let ext = file.extension().unwrap_or(&no_ext).to_string_lossy();
@@ -298,3 +859,422 @@ fn lookup_filename_in_vault<'a>(
             || path_normalized_lowered.ends_with(filename_normalized.to_lowercase() + ".md")
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unique, self-cleaning directory under the system temp dir, used to build a scratch
+    /// vault and destination for a single test.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "obsidian-copy-test-{name}-{}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn copy_creates_nested_destination_directories() {
+        let scratch = ScratchDir::new("nested-dirs");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(
+            root.join("sub/Note.md"),
+            "---\ntags:\n  - test\n---\n\n![[pic.png]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("sub/pic.png"), b"not a real image").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier.include_tags(vec!["test".to_string()]);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(dest.join("sub/Note.md").exists());
+        assert!(dest.join("sub/pic.png").exists());
+    }
+
+    #[test]
+    fn postprocessor_can_skip_a_note() {
+        let scratch = ScratchDir::new("postprocessor-skip");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("Note.md"), "---\ntags:\n  - test\n---\n\nBody\n").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .add_postprocessor(|_, _| PostprocessorResult::StopAndSkipNote);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(!dest.join("Note.md").exists());
+    }
+
+    #[test]
+    fn follow_links_false_excludes_linked_notes_but_keeps_attachments() {
+        let scratch = ScratchDir::new("follow-links-false");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Included.md"),
+            "---\ntags:\n  - test\n---\n\n[[Sibling]] and ![[pic.png]]\n",
+        )
+        .unwrap();
+        fs::write(root.join("Sibling.md"), "Untagged sibling note\n").unwrap();
+        fs::write(root.join("pic.png"), b"not a real image").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .follow_links(false);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(dest.join("Included.md").exists());
+        assert!(dest.join("pic.png").exists());
+        assert!(!dest.join("Sibling.md").exists());
+    }
+
+    #[test]
+    fn max_link_depth_bounds_transitive_expansion() {
+        let scratch = ScratchDir::new("max-link-depth");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("A.md"), "---\ntags:\n  - test\n---\n\n[[B]]\n").unwrap();
+        fs::write(root.join("B.md"), "[[C]]\n").unwrap();
+        fs::write(root.join("C.md"), "[[D]]\n").unwrap();
+        fs::write(root.join("D.md"), "Leaf note\n").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .max_link_depth(1);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(dest.join("A.md").exists());
+        assert!(dest.join("B.md").exists());
+        assert!(!dest.join("C.md").exists());
+        assert!(!dest.join("D.md").exists());
+    }
+
+    #[test]
+    fn max_link_depth_zero_follows_no_links() {
+        let scratch = ScratchDir::new("max-link-depth-zero");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("A.md"), "---\ntags:\n  - test\n---\n\n[[B]]\n").unwrap();
+        fs::write(root.join("B.md"), "Untagged note\n").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .max_link_depth(0);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(dest.join("A.md").exists());
+        assert!(!dest.join("B.md").exists());
+    }
+
+    #[test]
+    fn ignore_frontmatter_keyword_overrides_matching_tags() {
+        let scratch = ScratchDir::new("ignore-keyword");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Note.md"),
+            "---\ntags:\n  - test\nprivate: true\n---\n\nBody\n",
+        )
+        .unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier.include_tags(vec!["test".to_string()]);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(!dest.join("Note.md").exists());
+    }
+
+    #[test]
+    fn ignore_frontmatter_keyword_is_configurable() {
+        let scratch = ScratchDir::new("ignore-keyword-custom");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Note.md"),
+            "---\ntags:\n  - test\narchive: true\n---\n\nBody\n",
+        )
+        .unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .ignore_frontmatter_keyword("archive".to_string());
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(!dest.join("Note.md").exists());
+    }
+
+    #[test]
+    fn exclude_tags_take_precedence_over_include_tags() {
+        let scratch = ScratchDir::new("exclude-precedence");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("Note.md"),
+            "---\ntags:\n  - test\n  - archive\n---\n\nBody\n",
+        )
+        .unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .exclude_tags(vec!["archive".to_string()]);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        assert!(!dest.join("Note.md").exists());
+    }
+
+    #[test]
+    fn encode_link_path_escapes_special_characters() {
+        let path = Path::new("sub dir/My (Note).md");
+        assert_eq!(encode_link_path(path), "sub%20dir/My%20%28Note%29.md");
+    }
+
+    #[test]
+    fn slugify_fragment_matches_heading_anchor_conventions() {
+        assert_eq!(slugify_fragment("Hello, World!"), "hello-world");
+        assert_eq!(
+            slugify_fragment("  Leading and Trailing  "),
+            "leading-and-trailing"
+        );
+    }
+
+    #[test]
+    fn apply_frontmatter_strategy_auto_leaves_events_unchanged() {
+        let content = "---\ntags:\n  - test\n---\n\nBody\n";
+        let (frontmatter_str, mut events) = parse_events(content, Path::new("Note.md"));
+        let frontmatter = frontmatter::from_str(&frontmatter_str).unwrap();
+        let before = events.clone();
+
+        apply_frontmatter_strategy(&mut events, &frontmatter, FrontmatterStrategy::Auto);
+
+        assert_eq!(events, before);
+    }
+
+    #[test]
+    fn apply_frontmatter_strategy_never_strips_metadata_block() {
+        let content = "---\ntags:\n  - test\n---\n\nBody\n";
+        let (frontmatter_str, mut events) = parse_events(content, Path::new("Note.md"));
+        let frontmatter = frontmatter::from_str(&frontmatter_str).unwrap();
+
+        apply_frontmatter_strategy(&mut events, &frontmatter, FrontmatterStrategy::Never);
+
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, Event::Start(Tag::MetadataBlock(_)))));
+    }
+
+    #[test]
+    fn apply_frontmatter_strategy_always_synthesizes_metadata_block() {
+        let content = "Body with no frontmatter\n";
+        let (frontmatter_str, mut events) = parse_events(content, Path::new("Note.md"));
+        let frontmatter = frontmatter::from_str(&frontmatter_str).unwrap();
+
+        apply_frontmatter_strategy(&mut events, &frontmatter, FrontmatterStrategy::Always);
+
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::Start(Tag::MetadataBlock(_)))));
+    }
+
+    #[test]
+    fn rewrite_links_rewrites_wikilinks_to_relative_commonmark_links() {
+        let scratch = ScratchDir::new("rewrite-links");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(
+            root.join("sub/Note.md"),
+            "---\ntags:\n  - test\n---\n\nSee [[Other Note]].\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("Other Note.md"),
+            "---\ntags:\n  - test\n---\n\nOther body\n",
+        )
+        .unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier
+            .include_tags(vec!["test".to_string()])
+            .rewrite_links(true);
+
+        copier.index().unwrap();
+        copier.copy().unwrap();
+
+        let rendered = fs::read_to_string(dest.join("sub/Note.md")).unwrap();
+        assert!(rendered.contains("(../Other%20Note.md)"));
+    }
+
+    #[test]
+    fn vault_contents_includes_hidden_files_by_default() {
+        let scratch = ScratchDir::new("walk-hidden-default");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".hidden.md"), "hidden").unwrap();
+        fs::write(root.join("visible.md"), "visible").unwrap();
+
+        let contents = vault_contents(&root, &WalkOptions::default()).unwrap();
+
+        assert!(contents.contains(&root.join(".hidden.md")));
+        assert!(contents.contains(&root.join("visible.md")));
+    }
+
+    #[test]
+    fn walk_options_can_exclude_hidden_files() {
+        let scratch = ScratchDir::new("walk-hidden-excluded");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".hidden.md"), "hidden").unwrap();
+        fs::write(root.join("visible.md"), "visible").unwrap();
+
+        let contents = vault_contents(
+            &root,
+            &WalkOptions {
+                hidden: false,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!contents.contains(&root.join(".hidden.md")));
+        assert!(contents.contains(&root.join("visible.md")));
+    }
+
+    #[test]
+    fn walk_options_respects_gitignore_by_default() {
+        let scratch = ScratchDir::new("walk-gitignore-default");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "ignored.md\n").unwrap();
+        fs::write(root.join("ignored.md"), "ignored").unwrap();
+        fs::write(root.join("kept.md"), "kept").unwrap();
+
+        let contents = vault_contents(&root, &WalkOptions::default()).unwrap();
+
+        assert!(!contents.contains(&root.join("ignored.md")));
+        assert!(contents.contains(&root.join("kept.md")));
+    }
+
+    #[test]
+    fn walk_options_can_ignore_gitignore() {
+        let scratch = ScratchDir::new("walk-gitignore-disabled");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "ignored.md\n").unwrap();
+        fs::write(root.join("ignored.md"), "ignored").unwrap();
+
+        let contents = vault_contents(
+            &root,
+            &WalkOptions {
+                respect_gitignore: false,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(contents.contains(&root.join("ignored.md")));
+    }
+
+    #[test]
+    fn walk_options_respects_custom_ignore_filename() {
+        let scratch = ScratchDir::new("walk-custom-ignore");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".myignore"), "skip.md\n").unwrap();
+        fs::write(root.join("skip.md"), "skip").unwrap();
+        fs::write(root.join("keep.md"), "keep").unwrap();
+
+        let contents = vault_contents(
+            &root,
+            &WalkOptions {
+                custom_ignore_filename: Some(".myignore".to_string()),
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!contents.contains(&root.join("skip.md")));
+        assert!(contents.contains(&root.join("keep.md")));
+    }
+
+    #[test]
+    fn index_dedups_an_attachment_shared_by_many_included_notes() {
+        let scratch = ScratchDir::new("index-shared-attachment");
+        let root = scratch.path().join("vault");
+        fs::create_dir_all(&root).unwrap();
+        // Every note below independently references the same attachment; `index()` folds the
+        // per-note results (computed in parallel via rayon) into a single `to_copy` set, so this
+        // pins that the fold dedups rather than erroring or copying duplicate work.
+        for i in 0..20 {
+            fs::write(
+                root.join(format!("Note{i}.md")),
+                format!("---\ntags:\n  - test\n---\n\n![[shared.png]] note {i}\n"),
+            )
+            .unwrap();
+        }
+        fs::write(root.join("shared.png"), b"not a real image").unwrap();
+
+        let dest = scratch.path().join("dest");
+        let mut copier = Copier::new(root, dest.clone());
+        copier.include_tags(vec!["test".to_string()]);
+
+        copier.index().unwrap();
+        // 20 notes plus the one shared attachment.
+        assert_eq!(copier.to_copy.len(), 21);
+        copier.copy().unwrap();
+
+        assert!(dest.join("shared.png").exists());
+        for i in 0..20 {
+            assert!(dest.join(format!("Note{i}.md")).exists());
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use obsidian_copy::{Copier, CopyError};
+use obsidian_copy::{Copier, CopyError, WalkOptions};
 
 /// Copies part of an obsidian vault according to filters.
 #[derive(Parser, Debug)]
@@ -22,6 +22,18 @@ struct Args {
     /// Tags to exclude in copied vault
     #[arg(short, long)]
     exclude_tags: Vec<String>,
+
+    /// Don't include hidden files and directories
+    #[arg(long)]
+    no_hidden: bool,
+
+    /// Don't respect .gitignore files
+    #[arg(long)]
+    no_git: bool,
+
+    /// Name of an additional custom ignore file to respect, alongside .gitignore
+    #[arg(long = "ignore-file")]
+    ignore_file: Option<String>,
 }
 
 fn main() -> Result<(), CopyError> {
@@ -31,7 +43,12 @@ fn main() -> Result<(), CopyError> {
 
     copier
         .include_tags(args.include_tags)
-        .exclude_tags(args.exclude_tags);
+        .exclude_tags(args.exclude_tags)
+        .walk_options(WalkOptions {
+            hidden: !args.no_hidden,
+            respect_gitignore: !args.no_git,
+            custom_ignore_filename: args.ignore_file,
+        });
 
     copier.index()?;
     copier.copy()?;